@@ -1,23 +1,263 @@
 use wasm_bindgen::prelude::*;
-use image::{DynamicImage, ImageEncoder, ExtendedColorType};
-use image::codecs::{jpeg::JpegEncoder, png::PngEncoder, webp::WebPEncoder, bmp::BmpEncoder};
+use image::{DynamicImage, ImageEncoder, ImageFormat, ExtendedColorType};
+use image::codecs::{
+    jpeg::JpegEncoder, png::PngEncoder, webp::WebPEncoder, bmp::BmpEncoder, avif::AvifEncoder,
+};
 use rayon::prelude::*;
 use std::io::Cursor;
 use libheif_rs::{HeifContext, LibHeif, ColorSpace, RgbChroma};
 
+mod png_optimize;
+
+const DEFAULT_QUALITY: u8 = 60;
+const AVIF_ENCODE_SPEED: u8 = 4; // mid-point between size and encode time
+const DEFAULT_MAX_DIMENSION: u32 = 16_384;
+const DEFAULT_MAX_ALLOC_BYTES: u64 = 512 * 1024 * 1024; // 512 MiB
+
+/// Decode-time safety limits, applied before any pixel buffer is allocated.
+/// Without these a maliciously-crafted small file can claim an enormous
+/// width/height and OOM-kill the page it's running in.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct DecodeLimits {
+    max_width: u32,
+    max_height: u32,
+    max_alloc_bytes: u64,
+}
+
+#[wasm_bindgen]
+impl DecodeLimits {
+    #[wasm_bindgen(constructor)]
+    pub fn new(max_width: u32, max_height: u32, max_alloc_bytes: u64) -> DecodeLimits {
+        DecodeLimits { max_width, max_height, max_alloc_bytes }
+    }
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        DecodeLimits {
+            max_width: DEFAULT_MAX_DIMENSION,
+            max_height: DEFAULT_MAX_DIMENSION,
+            max_alloc_bytes: DEFAULT_MAX_ALLOC_BYTES,
+        }
+    }
+}
+
+impl DecodeLimits {
+    fn to_image_limits(self) -> image::Limits {
+        let mut limits = image::Limits::no_limits();
+        limits.max_image_width = Some(self.max_width);
+        limits.max_image_height = Some(self.max_height);
+        limits.max_alloc = Some(self.max_alloc_bytes);
+        limits
+    }
+
+    /// Manual width/height check used by the lossy-recovery path once the
+    /// decoder has reported the dimensions (`decode_heic` does the same
+    /// check inline).
+    fn check_dimensions(self, width: u32, height: u32) -> Result<(), JsValue> {
+        if width > self.max_width || height > self.max_height {
+            return Err(JsValue::from_str(&format!(
+                "La imagen ({}x{}) excede el límite permitido ({}x{})",
+                width, height, self.max_width, self.max_height
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Encode-time knobs exposed to JS callers. `quality` drives JPEG/AVIF
+/// compression (WebP output is always lossless — see the `"webp"` arm of
+/// `resize_and_encode`). Passing `"auto"` as the `format` argument of
+/// `resize_image` (not a field here) picks a lossy or lossless output based on
+/// how lossy the *source* already is, the same idea as the
+/// `Format::from_args` helper in zola's imageproc. `optimize` runs a
+/// lossless post-process over PNG output (see `png_optimize`).
 #[wasm_bindgen]
-pub fn resize_image(image_data: &[u8], max_width: u32, max_height: u32, format: &str) -> Vec<u8> {
+#[derive(Clone, Copy, Debug)]
+pub struct EncodeOptions {
+    quality: u8,
+    optimize: bool,
+    tiff_compression: TiffCompression,
+}
+
+#[wasm_bindgen]
+impl EncodeOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new(quality: u8, optimize: bool, tiff_compression: TiffCompression) -> EncodeOptions {
+        EncodeOptions { quality: quality.clamp(1, 100), optimize, tiff_compression }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn quality(&self) -> u8 {
+        self.quality
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn optimize(&self) -> bool {
+        self.optimize
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn tiff_compression(&self) -> TiffCompression {
+        self.tiff_compression
+    }
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        EncodeOptions { quality: DEFAULT_QUALITY, optimize: false, tiff_compression: TiffCompression::Lzw }
+    }
+}
+
+/// Compression scheme for TIFF output, mirroring the options the `tiff`
+/// crate's encoder exposes: `Lzw`/`Deflate` for smaller archival files,
+/// `PackBits`/`Uncompressed` for maximum reader compatibility.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TiffCompression {
+    Uncompressed,
+    Lzw,
+    Deflate,
+    PackBits,
+}
+
+/// `image`'s own `TiffEncoder` has no compression-selection API, so TIFF
+/// output is written directly against the `tiff` crate's encoder instead,
+/// picking the concrete `Compression` type the caller asked for.
+fn write_tiff(
+    cursor: &mut Cursor<&mut Vec<u8>>,
+    width: u32,
+    height: u32,
+    rgb: &[u8],
+    compression: TiffCompression,
+) -> Result<(), JsValue> {
+    use tiff::encoder::{colortype::RGB8, compression, TiffEncoder};
+
+    let tiff_err = |e: tiff::TiffError| JsValue::from_str(&format!("Error al codificar TIFF: {}", e));
+    let mut encoder = TiffEncoder::new(cursor).map_err(|e| {
+        JsValue::from_str(&format!("Error al inicializar el codificador TIFF: {}", e))
+    })?;
+
+    match compression {
+        TiffCompression::Uncompressed => encoder
+            .new_image_with_compression::<RGB8, _>(width, height, compression::Uncompressed)
+            .and_then(|mut image| image.write_data(rgb))
+            .map_err(tiff_err),
+        TiffCompression::Lzw => encoder
+            .new_image_with_compression::<RGB8, _>(width, height, compression::Lzw::default())
+            .and_then(|mut image| image.write_data(rgb))
+            .map_err(tiff_err),
+        TiffCompression::Deflate => encoder
+            .new_image_with_compression::<RGB8, _>(width, height, compression::Deflate::default())
+            .and_then(|mut image| image.write_data(rgb))
+            .map_err(tiff_err),
+        TiffCompression::PackBits => encoder
+            .new_image_with_compression::<RGB8, _>(width, height, compression::Packbits)
+            .and_then(|mut image| image.write_data(rgb))
+            .map_err(tiff_err),
+    }
+}
+
+/// Picks a concrete output format for `format == "auto"`: sources that are
+/// already lossy (JPEG/WebP/AVIF) stay lossy, everything else (PNG/BMP) is
+/// treated as lossless and re-encoded as PNG. HEIC sources are routed to
+/// "jpeg" separately, before this is ever called (see the `"heic"` arm in
+/// `resize_and_encode`).
+///
+/// Known simplification: `ImageFormat::WebP` doesn't distinguish the lossy
+/// (VP8) and lossless (VP8L) sub-formats, so a lossless-WebP source is
+/// treated the same as a lossy one here and re-encoded as JPEG rather than
+/// PNG. Telling them apart needs a peek at the RIFF chunk FourCC in the raw
+/// bytes, not just the `image::ImageFormat` the decoder reports.
+fn resolve_auto_format(source_format: Option<ImageFormat>) -> &'static str {
+    match source_format {
+        Some(ImageFormat::Jpeg) | Some(ImageFormat::WebP) | Some(ImageFormat::Avif) => "jpeg",
+        _ => "png",
+    }
+}
+
+/// Decodes `image_data` according to the `format` hint, same dispatch used
+/// by both `resize_image` and `resize_image_lossy`: `"heic"` goes through
+/// `libheif`, everything else is sniffed by the `image` crate.
+fn decode_image(
+    image_data: &[u8],
+    format: &str,
+    limits: DecodeLimits,
+) -> Result<(DynamicImage, Option<ImageFormat>), JsValue> {
+    let source_format = image::guess_format(image_data).ok();
+
     let img = match format {
-        "heic" => match decode_heic(image_data) {
-            Ok(img) => img,
-            Err(e) => panic!("Error al decodificar HEIC: {}", e),
-        },
-        _ => match image::load_from_memory(image_data) {
-            Ok(img) => img,
-            Err(_) => panic!("Formato no soportado: {}", format),
-        },
+        "heic" => decode_heic(image_data, limits)
+            .map_err(|e| JsValue::from_str(&format!("Error al decodificar HEIC: {}", e)))?,
+        "exr" => {
+            let mut reader = image::ImageReader::new(Cursor::new(image_data))
+                .with_guessed_format()
+                .map_err(|e| JsValue::from_str(&format!("Formato no soportado: {}", e)))?;
+            reader.limits(limits.to_image_limits());
+            let hdr = reader
+                .decode()
+                .map_err(|e| JsValue::from_str(&format!("No se pudo decodificar el OpenEXR: {}", e)))?;
+            tone_map_hdr(hdr)
+        }
+        _ => {
+            let mut reader = image::ImageReader::new(Cursor::new(image_data))
+                .with_guessed_format()
+                .map_err(|e| JsValue::from_str(&format!("Formato no soportado: {}", e)))?;
+            reader.limits(limits.to_image_limits());
+            reader
+                .decode()
+                .map_err(|e| JsValue::from_str(&format!("No se pudo decodificar la imagen: {}", e)))?
+        }
     };
 
+    Ok((img, source_format))
+}
+
+/// Tone-maps a decoded HDR (float) image down to 8-bit before it hits
+/// the normal `to_rgb8()` resize/encode path. Uses a simple Reinhard
+/// operator (`c / (1 + c)`) — cheap, and well-behaved for arbitrary
+/// unbounded float input, unlike a naive clip-and-cast. The mapped value is
+/// scaled straight to 0–255; no sRGB transfer curve is applied.
+fn tone_map_hdr(img: DynamicImage) -> DynamicImage {
+    fn reinhard(channel: f32) -> u8 {
+        let mapped = channel.max(0.0);
+        ((mapped / (1.0 + mapped)) * 255.0).round() as u8
+    }
+
+    match img {
+        DynamicImage::ImageRgb32F(buf) => {
+            let (width, height) = buf.dimensions();
+            let mut out = image::RgbImage::new(width, height);
+            for (x, y, pixel) in buf.enumerate_pixels() {
+                out.put_pixel(x, y, image::Rgb([reinhard(pixel[0]), reinhard(pixel[1]), reinhard(pixel[2])]));
+            }
+            DynamicImage::ImageRgb8(out)
+        }
+        DynamicImage::ImageRgba32F(buf) => {
+            let (width, height) = buf.dimensions();
+            let mut out = image::RgbaImage::new(width, height);
+            for (x, y, pixel) in buf.enumerate_pixels() {
+                let alpha = (pixel[3].clamp(0.0, 1.0) * 255.0).round() as u8;
+                out.put_pixel(x, y, image::Rgba([reinhard(pixel[0]), reinhard(pixel[1]), reinhard(pixel[2]), alpha]));
+            }
+            DynamicImage::ImageRgba8(out)
+        }
+        already_ldr => already_ldr,
+    }
+}
+
+/// Resizes (if needed) and encodes `img` into `format`, applying `options`.
+/// Shared by `resize_image` and `resize_image_lossy` so the two entry
+/// points can't drift on encoder settings.
+fn resize_and_encode(
+    img: DynamicImage,
+    max_width: u32,
+    max_height: u32,
+    format: &str,
+    source_format: Option<ImageFormat>,
+    options: EncodeOptions,
+) -> Result<Vec<u8>, JsValue> {
     let resized = if img.width() > max_width || img.height() > max_height {
         img.resize(max_width, max_height, image::imageops::FilterType::Lanczos3)
     } else {
@@ -31,33 +271,182 @@ pub fn resize_image(image_data: &[u8], max_width: u32, max_height: u32, format:
     let mut buf = Vec::new();
     let mut cursor = Cursor::new(&mut buf);
 
-    let target_format = if format == "heic" { "jpeg" } else { format };
+    let quality = options.quality;
+
+    let target_format = match format {
+        "heic" | "exr" => "jpeg",
+        "auto" => resolve_auto_format(source_format),
+        other => other,
+    };
+
+    let encode_err = |e: image::ImageError| JsValue::from_str(&format!("Error al codificar la imagen: {}", e));
 
     match target_format {
         "jpeg" | "jpg" => {
-            let encoder = JpegEncoder::new_with_quality(&mut cursor, 60);
-            encoder.write_image(&raw_bytes, new_width, new_height, ExtendedColorType::Rgb8).unwrap();
+            let encoder = JpegEncoder::new_with_quality(&mut cursor, quality);
+            encoder.write_image(&raw_bytes, new_width, new_height, ExtendedColorType::Rgb8).map_err(encode_err)?;
         }
         "png" => {
             let encoder = PngEncoder::new(&mut cursor);
-            encoder.write_image(&raw_bytes, new_width, new_height, ExtendedColorType::Rgb8).unwrap();
+            encoder.write_image(&raw_bytes, new_width, new_height, ExtendedColorType::Rgb8).map_err(encode_err)?;
+            if options.optimize {
+                buf = png_optimize::optimize(&buf);
+            }
         }
         "webp" => {
+            // image's WebPEncoder only implements lossless (VP8L) encoding —
+            // there is no quality knob to apply here; see `new_lossless`'s docs.
             let encoder = WebPEncoder::new_lossless(&mut cursor);
-            encoder.write_image(&raw_bytes, new_width, new_height, ExtendedColorType::Rgb8).unwrap();
+            encoder.write_image(&raw_bytes, new_width, new_height, ExtendedColorType::Rgb8).map_err(encode_err)?;
+        }
+        "avif" => {
+            let encoder = AvifEncoder::new_with_speed_quality(&mut cursor, AVIF_ENCODE_SPEED, quality);
+            encoder.write_image(&raw_bytes, new_width, new_height, ExtendedColorType::Rgb8).map_err(encode_err)?;
+        }
+        "tiff" | "tif" => {
+            write_tiff(&mut cursor, new_width, new_height, &raw_bytes, options.tiff_compression)?;
         }
         "bmp" => {
             let encoder = BmpEncoder::new(&mut cursor);
-            encoder.write_image(&raw_bytes, new_width, new_height, ExtendedColorType::Rgb8).unwrap();
+            encoder.write_image(&raw_bytes, new_width, new_height, ExtendedColorType::Rgb8).map_err(encode_err)?;
         }
-        _ => panic!("Formato no soportado: {}", format),
+        _ => return Err(JsValue::from_str(&format!("Formato no soportado: {}", format))),
     }
 
-    buf
+    Ok(buf)
+}
+
+#[wasm_bindgen]
+pub fn resize_image(
+    image_data: &[u8],
+    max_width: u32,
+    max_height: u32,
+    format: &str,
+    options: Option<EncodeOptions>,
+    limits: Option<DecodeLimits>,
+) -> Result<Vec<u8>, JsValue> {
+    let (img, source_format) = decode_image(image_data, format, limits.unwrap_or_default())?;
+    resize_and_encode(img, max_width, max_height, format, source_format, options.unwrap_or_default())
+}
+
+/// Result of `resize_image_lossy`: the encoded image plus whether the
+/// source had to be recovered from a truncated/corrupt decode.
+#[wasm_bindgen]
+pub struct LossyResizeResult {
+    data: Vec<u8>,
+    partial: bool,
+}
+
+#[wasm_bindgen]
+impl LossyResizeResult {
+    #[wasm_bindgen(getter)]
+    pub fn data(&self) -> Vec<u8> {
+        self.data.clone()
+    }
+
+    /// `true` when the source image was truncated or malformed and the
+    /// decode did not fully succeed. Undecoded pixels default to 0; for
+    /// codecs that fill scanlines incrementally (e.g. JPEG) this usually
+    /// means a real partial thumbnail, but it is not guaranteed for every
+    /// codec — see `decode_image_lossy`.
+    #[wasm_bindgen(getter)]
+    pub fn partial(&self) -> bool {
+        self.partial
+    }
+}
+
+/// Like `resize_image`, but tolerates truncated/corrupt uploads: once the
+/// decoder knows the image's dimensions it keeps whatever rows it managed
+/// to decode instead of aborting, filling the rest with zeroed pixels, the
+/// same recovery strategy as image-rs's `load_lossy` test helper. Use this
+/// for user uploads where returning *something* (even a blank or partial
+/// thumbnail, flagged via `LossyResizeResult::partial`) beats a hard
+/// failure.
+#[wasm_bindgen]
+pub fn resize_image_lossy(
+    image_data: &[u8],
+    max_width: u32,
+    max_height: u32,
+    format: &str,
+    options: Option<EncodeOptions>,
+    limits: Option<DecodeLimits>,
+) -> Result<LossyResizeResult, JsValue> {
+    let limits = limits.unwrap_or_default();
+    let (img, source_format, partial) = decode_image_lossy(image_data, format, limits)?;
+    let data = resize_and_encode(img, max_width, max_height, format, source_format, options.unwrap_or_default())?;
+    Ok(LossyResizeResult { data, partial })
+}
+
+/// Decodes with error recovery: reads the header to learn the dimensions
+/// and color type, allocates the pixel buffer up front, then attempts the
+/// full decode into it. `ImageDecoder::read_image` gives no cross-codec
+/// guarantee about how much of the buffer is filled before it errors out —
+/// for JPEG (the common truncated-upload case) the underlying decoder fills
+/// scanlines top-to-bottom as it goes, so a cut-off file keeps the rows it
+/// reached and zeroes the rest. Codecs that buffer internally and only
+/// write on success may instead leave the whole buffer zeroed (a blank
+/// image) on the same `partial: true` result, so callers should treat
+/// `partial` as "decoding was not fully trustworthy", not as a promise of a
+/// non-blank thumbnail.
+fn decode_image_lossy(
+    image_data: &[u8],
+    format: &str,
+    limits: DecodeLimits,
+) -> Result<(DynamicImage, Option<ImageFormat>, bool), JsValue> {
+    if format == "heic" {
+        // libheif has no equivalent partial-decode API; fall back to the
+        // regular strict path for HEIC sources.
+        let (img, source_format) = decode_image(image_data, format, limits)?;
+        return Ok((img, source_format, false));
+    }
+
+    let source_format = image::guess_format(image_data).ok();
+
+    let reader = image::ImageReader::new(Cursor::new(image_data))
+        .with_guessed_format()
+        .map_err(|e| JsValue::from_str(&format!("Formato no soportado: {}", e)))?;
+
+    let mut decoder = reader
+        .into_decoder()
+        .map_err(|e| JsValue::from_str(&format!("No se pudo leer el encabezado de la imagen: {}", e)))?;
+    image::ImageDecoder::set_limits(&mut decoder, limits.to_image_limits())
+        .map_err(|e| JsValue::from_str(&format!("La imagen excede los límites de decodificación: {}", e)))?;
+
+    let (width, height) = image::ImageDecoder::dimensions(&decoder);
+    limits.check_dimensions(width, height)?;
+    let color_type = image::ImageDecoder::color_type(&decoder);
+    let mut buf = vec![0u8; image::ImageDecoder::total_bytes(&decoder) as usize];
+
+    let partial = match image::ImageDecoder::read_image(decoder, &mut buf) {
+        Ok(()) => false,
+        Err(_) => true,
+    };
+
+    let img = buffer_to_dynamic_image(width, height, color_type, buf)
+        .ok_or_else(|| JsValue::from_str("No se pudo reconstruir la imagen parcial"))?;
+
+    Ok((img, source_format, partial))
+}
+
+/// Wraps a raw pixel buffer (as produced by `ImageDecoder::read_image`)
+/// back into a `DynamicImage`, dispatching on the decoder's native color
+/// type. Only 8-bit L/LA/RGB/RGBA buffers are handled; anything else
+/// (16-bit or float sources such as OpenEXR) returns `None` rather than
+/// silently misinterpreting bytes.
+fn buffer_to_dynamic_image(width: u32, height: u32, color_type: image::ColorType, buf: Vec<u8>) -> Option<DynamicImage> {
+    use image::ColorType;
+
+    match color_type {
+        ColorType::L8 => image::GrayImage::from_raw(width, height, buf).map(DynamicImage::ImageLuma8),
+        ColorType::La8 => image::GrayAlphaImage::from_raw(width, height, buf).map(DynamicImage::ImageLumaA8),
+        ColorType::Rgb8 => image::RgbImage::from_raw(width, height, buf).map(DynamicImage::ImageRgb8),
+        ColorType::Rgba8 => image::RgbaImage::from_raw(width, height, buf).map(DynamicImage::ImageRgba8),
+        _ => None,
+    }
 }
 
 // 📝 Función corregida para decodificar HEIC con `libheif-rs v1.1.0`
-fn decode_heic(image_data: &[u8]) -> Result<DynamicImage, String> {
+fn decode_heic(image_data: &[u8], limits: DecodeLimits) -> Result<DynamicImage, String> {
     let lib_heif = LibHeif::new();
     let ctx = HeifContext::read_from_bytes(image_data)
         .map_err(|e| format!("Error al leer HEIC: {}", e))?;
@@ -65,6 +454,15 @@ fn decode_heic(image_data: &[u8]) -> Result<DynamicImage, String> {
     let img_handle = ctx.primary_image_handle()
         .map_err(|e| format!("No se encontró la imagen principal en el HEIC: {}", e))?;
 
+    // Bound the dimensions before `lib_heif.decode` allocates the pixel
+    // buffer, so an oversized HEIC fails fast instead of exhausting memory.
+    if img_handle.width() > limits.max_width || img_handle.height() > limits.max_height {
+        return Err(format!(
+            "La imagen HEIC ({}x{}) excede el límite permitido ({}x{})",
+            img_handle.width(), img_handle.height(), limits.max_width, limits.max_height
+        ));
+    }
+
     let decoded_image = lib_heif.decode(
         &img_handle,
         ColorSpace::Rgb(RgbChroma::Rgb), // ✅ Nueva sintaxis
@@ -103,7 +501,7 @@ mod tests {
     fn test_resize_image_reduces_large_images() {
         let image_data = load_test_image("test_images/test_4k.jpg");
 
-        let output = resize_image(&image_data, 1920, 1080, "jpeg");
+        let output = resize_image(&image_data, 1920, 1080, "jpeg", None, None).expect("resize_image debe tener éxito");
         assert!(!output.is_empty(), "La imagen procesada no debe estar vacía");
 
         assert!(output.len() < image_data.len(), "La imagen optimizada debe ser más pequeña que la original");
@@ -113,7 +511,7 @@ mod tests {
     fn test_resize_image_keeps_hd_images() {
         let image_data = load_test_image("test_images/test_hd.jpg");
 
-        let output = resize_image(&image_data, 1920, 1080, "jpeg");
+        let output = resize_image(&image_data, 1920, 1080, "jpeg", None, None).expect("resize_image debe tener éxito");
         assert!(!output.is_empty(), "La imagen procesada no debe estar vacía");
 
         let original_size = image_data.len();
@@ -139,7 +537,7 @@ mod tests {
     fn test_output_is_valid_jpeg() {
         let image_data = load_test_image("test_images/test_4k.jpg");
 
-        let output = resize_image(&image_data, 1920, 1080, "jpeg");
+        let output = resize_image(&image_data, 1920, 1080, "jpeg", None, None).expect("resize_image debe tener éxito");
         let cursor = Cursor::new(output);
 
         let img_result = ImageReader::new(cursor)
@@ -154,7 +552,7 @@ mod tests {
     fn test_resize_image_handles_extremely_large_images() {
         let image_data = load_test_image("test_images/test_8k.jpg");
 
-        let output = resize_image(&image_data, 1920, 1080, "jpeg");
+        let output = resize_image(&image_data, 1920, 1080, "jpeg", None, None).expect("resize_image debe tener éxito");
         assert!(!output.is_empty(), "La imagen procesada no debe estar vacía");
 
         let img_result = ImageReader::new(Cursor::new(output))
@@ -169,7 +567,7 @@ mod tests {
     fn test_resize_image_converts_non_rgb_images() {
         let image_data = load_test_image("test_images/test_grayscale.jpg");
 
-        let output = resize_image(&image_data, 1920, 1080, "jpeg");
+        let output = resize_image(&image_data, 1920, 1080, "jpeg", None, None).expect("resize_image debe tener éxito");
         assert!(!output.is_empty(), "La imagen procesada no debe estar vacía");
 
         let img_result = ImageReader::new(Cursor::new(output))
@@ -184,7 +582,7 @@ mod tests {
     fn test_output_is_valid_webp() {
         let image_data = load_test_image("test_images/test_4k.jpg");
 
-        let output = resize_image(&image_data, 1920, 1080, "webp");
+        let output = resize_image(&image_data, 1920, 1080, "webp", None, None).expect("resize_image debe tener éxito");
         let cursor = Cursor::new(output);
 
         let img_result = ImageReader::new(cursor)
@@ -195,11 +593,40 @@ mod tests {
         assert!(img_result.is_ok(), "La imagen resultante debe ser un WebP válido");
     }
 
+    #[test]
+    fn test_png_optimize_produces_valid_png_no_larger() {
+        let image_data = load_test_image("test_images/test_4k.jpg");
+
+        let plain = resize_image(&image_data, 1920, 1080, "png", None, None)
+            .expect("resize_image debe tener éxito");
+        let optimized = resize_image(
+            &image_data,
+            1920,
+            1080,
+            "png",
+            Some(EncodeOptions::new(DEFAULT_QUALITY, true, TiffCompression::Lzw)),
+            None,
+        )
+        .expect("resize_image debe tener éxito");
+
+        assert!(
+            optimized.len() <= plain.len(),
+            "El PNG optimizado no debe ser más grande que el PNG sin optimizar"
+        );
+
+        let img_result = ImageReader::new(Cursor::new(optimized))
+            .with_guessed_format()
+            .expect("Failed to read image format")
+            .decode();
+
+        assert!(img_result.is_ok(), "El PNG optimizado debe seguir siendo válido");
+    }
+
     #[test]
     fn test_output_is_valid_bmp() {
         let image_data = load_test_image("test_images/test_4k.jpg");
 
-        let output = resize_image(&image_data, 1920, 1080, "bmp");
+        let output = resize_image(&image_data, 1920, 1080, "bmp", None, None).expect("resize_image debe tener éxito");
         let cursor = Cursor::new(output);
 
         let img_result = ImageReader::new(cursor)
@@ -210,11 +637,70 @@ mod tests {
         assert!(img_result.is_ok(), "La imagen resultante debe ser un BMP válido");
     }
 
+    #[test]
+    fn test_output_is_valid_avif() {
+        let image_data = load_test_image("test_images/test_4k.jpg");
+
+        let output = resize_image(&image_data, 1920, 1080, "avif", None, None).expect("resize_image debe tener éxito");
+        let cursor = Cursor::new(output);
+
+        let img_result = ImageReader::new(cursor)
+            .with_guessed_format()
+            .expect("Failed to read image format")
+            .decode();
+
+        assert!(img_result.is_ok(), "La imagen resultante debe ser un AVIF válido");
+    }
+
+    #[test]
+    fn test_output_is_valid_tiff_with_selected_compression() {
+        let image_data = load_test_image("test_images/test_4k.jpg");
+
+        for compression in [
+            TiffCompression::Uncompressed,
+            TiffCompression::Lzw,
+            TiffCompression::Deflate,
+            TiffCompression::PackBits,
+        ] {
+            let output = resize_image(
+                &image_data,
+                1920,
+                1080,
+                "tiff",
+                Some(EncodeOptions::new(DEFAULT_QUALITY, false, compression)),
+                None,
+            )
+            .expect("resize_image debe tener éxito");
+
+            let img_result = ImageReader::new(Cursor::new(output))
+                .with_guessed_format()
+                .expect("Failed to read image format")
+                .decode();
+
+            assert!(img_result.is_ok(), "La imagen TIFF resultante debe ser válida con {:?}", compression);
+        }
+    }
+
+    #[test]
+    fn test_resize_image_converts_exr_to_jpeg() {
+        let image_data = load_test_image("test_images/test_hdr.exr");
+
+        let output = resize_image(&image_data, 1920, 1080, "exr", None, None).expect("resize_image debe tener éxito");
+        let cursor = Cursor::new(output);
+
+        let img_result = ImageReader::new(cursor)
+            .with_guessed_format()
+            .expect("Failed to read image format")
+            .decode();
+
+        assert!(img_result.is_ok(), "La imagen HDR debe tonemapearse y convertirse correctamente");
+    }
+
     #[test]
     fn test_output_is_valid_heic() {
         let image_data = load_test_image("test_images/test_heic.heic");
 
-        let output = resize_image(&image_data, 1920, 1080, "heic");
+        let output = resize_image(&image_data, 1920, 1080, "heic", None, None).expect("resize_image debe tener éxito");
         let cursor = Cursor::new(output);
 
         let img_result = ImageReader::new(cursor)
@@ -229,7 +715,7 @@ mod tests {
     fn test_resized_image_has_correct_dimensions() {
         let image_data = load_test_image("test_images/test_4k.jpg");
 
-        let output = resize_image(&image_data, 1920, 1080, "jpeg");
+        let output = resize_image(&image_data, 1920, 1080, "jpeg", None, None).expect("resize_image debe tener éxito");
         let cursor = Cursor::new(output);
 
         let img = ImageReader::new(cursor)
@@ -241,4 +727,41 @@ mod tests {
         let (width, height) = img.dimensions();
         assert!(width <= 1920 && height <= 1080, "Las dimensiones de la imagen deben estar dentro del límite");
     }
+
+    #[test]
+    fn test_resize_image_lossy_recovers_truncated_upload() {
+        let image_data = load_test_image("test_images/test_4k.jpg");
+        let truncated = &image_data[..image_data.len() / 2];
+
+        let result = resize_image_lossy(truncated, 1920, 1080, "jpeg", None, None)
+            .expect("resize_image_lossy debe recuperar un archivo truncado");
+
+        assert!(result.partial(), "Un JPEG truncado debe marcarse como parcial");
+        // El JPEG decoder de image-rs rellena las filas de forma incremental,
+        // así que normalmente sale una miniatura parcial real y no un buffer en
+        // blanco; otros códecs no dan esa garantía (ver el comentario de
+        // decode_image_lossy). Aquí solo se comprueba que haya salida codificada.
+        assert!(!result.data().is_empty(), "Debe devolver una salida codificada aunque sea parcial");
+    }
+
+    #[test]
+    fn test_resize_image_lossy_matches_strict_path_on_healthy_input() {
+        let image_data = load_test_image("test_images/test_4k.jpg");
+
+        let result = resize_image_lossy(&image_data, 1920, 1080, "jpeg", None, None)
+            .expect("resize_image_lossy debe tener éxito");
+
+        assert!(!result.partial(), "Una imagen sana no debe marcarse como parcial");
+        assert!(!result.data().is_empty(), "La imagen procesada no debe estar vacía");
+    }
+
+    #[test]
+    fn test_decode_limits_reject_oversized_image() {
+        let image_data = load_test_image("test_images/test_4k.jpg");
+        let tiny_limits = DecodeLimits::new(16, 16, DEFAULT_MAX_ALLOC_BYTES);
+
+        let result = resize_image(&image_data, 1920, 1080, "jpeg", None, Some(tiny_limits));
+
+        assert!(result.is_err(), "Una imagen que excede los límites debe fallar en vez de intentar decodificarse");
+    }
 }
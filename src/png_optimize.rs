@@ -0,0 +1,328 @@
+//! A small, self-contained PNG re-optimizer used when callers opt in with
+//! `EncodeOptions { optimize: true, .. }` on a `png` `resize_image` call.
+//!
+//! This mirrors oxipng's approach at a much smaller scale: re-filter every
+//! scanline with each of the five standard PNG filter types, keep for each
+//! scanline the filter with the lowest sum-of-absolute-values score (the
+//! candidates are not deflated one by one), deflate the chosen stream once at
+//! maximum compression, keep it only if it beats the original IDAT, and
+//! drop ancillary chunks (tEXt/tIME/etc.) that don't affect pixels. When the
+//! image has a small enough palette we also fold it down to an indexed (color
+//! type 3) PNG, since that's byte-identical in the pixels it represents but
+//! cheaper to store. The output is always a spec-valid PNG decoding to the
+//! exact same pixels as the input.
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
+const FILTER_TYPES: [u8; 5] = [0, 1, 2, 3, 4]; // None, Sub, Up, Average, Paeth
+
+/// Ancillary chunks that are always safe to drop when re-packing a PNG:
+/// purely textual/administrative metadata with no effect on how the pixels
+/// are rendered. Deliberately excludes `gAMA`/`cHRM`/`sRGB`/`iCCP` (color
+/// management — stripping them changes the *displayed* color even though
+/// the raw samples are untouched) and `eXIf` (may carry an orientation tag
+/// that affects how viewers present the image).
+fn is_ancillary(kind: &[u8; 4]) -> bool {
+    matches!(kind, b"tEXt" | b"zTXt" | b"iTXt" | b"tIME" | b"pHYs")
+}
+
+struct Chunk {
+    kind: [u8; 4],
+    data: Vec<u8>,
+}
+
+fn parse_chunks(png: &[u8]) -> Option<Vec<Chunk>> {
+    if png.len() < 8 || png[..8] != PNG_SIGNATURE {
+        return None;
+    }
+    let mut chunks = Vec::new();
+    let mut pos = 8;
+    while pos + 8 <= png.len() {
+        let len = u32::from_be_bytes(png[pos..pos + 4].try_into().ok()?) as usize;
+        let kind: [u8; 4] = png[pos + 4..pos + 8].try_into().ok()?;
+        let data_start = pos + 8;
+        let data_end = data_start.checked_add(len)?;
+        if data_end + 4 > png.len() {
+            return None;
+        }
+        chunks.push(Chunk { kind, data: png[data_start..data_end].to_vec() });
+        pos = data_end + 4; // skip the trailing CRC
+        if &kind == b"IEND" {
+            break;
+        }
+    }
+    Some(chunks)
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(kind);
+    hasher.update(data);
+    out.extend_from_slice(&hasher.finalize().to_be_bytes());
+}
+
+fn paeth_predictor(a: i16, b: i16, c: i16) -> u8 {
+    let p = a + b - c;
+    let pa = (p - a).abs();
+    let pb = (p - b).abs();
+    let pc = (p - c).abs();
+    if pa <= pb && pa <= pc {
+        a as u8
+    } else if pb <= pc {
+        b as u8
+    } else {
+        c as u8
+    }
+}
+
+fn unfilter_row(filter: u8, row: &[u8], prev: &[u8], bpp: usize) -> Vec<u8> {
+    let mut out = vec![0u8; row.len()];
+    for i in 0..row.len() {
+        let a = if i >= bpp { out[i - bpp] as i16 } else { 0 };
+        let b = prev.get(i).copied().unwrap_or(0) as i16;
+        let c = if i >= bpp { prev.get(i - bpp).copied().unwrap_or(0) as i16 } else { 0 };
+        let x = row[i] as i16;
+        let value = match filter {
+            0 => x,
+            1 => x + a,
+            2 => x + b,
+            3 => x + (a + b) / 2,
+            4 => x + paeth_predictor(a, b, c) as i16,
+            _ => x,
+        };
+        out[i] = value as u8;
+    }
+    out
+}
+
+fn filter_row(filter: u8, row: &[u8], prev: &[u8], bpp: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(row.len());
+    for i in 0..row.len() {
+        let x = row[i] as i16;
+        let a = if i >= bpp { row[i - bpp] as i16 } else { 0 };
+        let b = prev.get(i).copied().unwrap_or(0) as i16;
+        let c = if i >= bpp { prev.get(i - bpp).copied().unwrap_or(0) as i16 } else { 0 };
+        let value = match filter {
+            0 => x,
+            1 => x - a,
+            2 => x - b,
+            3 => x - (a + b) / 2,
+            4 => x - paeth_predictor(a, b, c) as i16,
+            _ => x,
+        };
+        out.push(value as u8);
+    }
+    out
+}
+
+/// Sum of absolute values treated as signed bytes: the usual heuristic for
+/// picking the filter that deflates best without actually running deflate.
+fn filter_heuristic(row: &[u8]) -> u32 {
+    row.iter().map(|&b| (b as i8).unsigned_abs() as u32).sum()
+}
+
+fn unfilter_scanlines(raw: &[u8], stride: usize, bpp: usize) -> Vec<Vec<u8>> {
+    let mut rows = Vec::with_capacity(raw.len() / (stride + 1).max(1));
+    let mut prev = vec![0u8; stride];
+    let mut pos = 0;
+    while pos + 1 + stride <= raw.len() {
+        let filter = raw[pos];
+        let row = &raw[pos + 1..pos + 1 + stride];
+        let unfiltered = unfilter_row(filter, row, &prev, bpp);
+        prev = unfiltered.clone();
+        rows.push(unfiltered);
+        pos += 1 + stride;
+    }
+    rows
+}
+
+fn best_filtered_stream(rows: &[Vec<u8>], bpp: usize) -> Vec<u8> {
+    let stride = rows.first().map(|r| r.len()).unwrap_or(0);
+    let zero_row = vec![0u8; stride];
+    let mut out = Vec::with_capacity(rows.len() * (stride + 1));
+    let mut prev = &zero_row;
+    for row in rows {
+        let mut best_filter = 0u8;
+        let mut best_row = filter_row(0, row, prev, bpp);
+        let mut best_score = filter_heuristic(&best_row);
+        for &f in &FILTER_TYPES[1..] {
+            let candidate = filter_row(f, row, prev, bpp);
+            let score = filter_heuristic(&candidate);
+            if score < best_score {
+                best_score = score;
+                best_filter = f;
+                best_row = candidate;
+            }
+        }
+        out.push(best_filter);
+        out.extend_from_slice(&best_row);
+        prev = row;
+    }
+    out
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(data).expect("zlib encoding into a Vec cannot fail");
+    encoder.finish().expect("zlib encoding into a Vec cannot fail")
+}
+
+fn inflate(data: &[u8], expected_len: usize) -> Option<Vec<u8>> {
+    let mut decoder = ZlibDecoder::new(data);
+    let mut out = Vec::with_capacity(expected_len);
+    decoder.read_to_end(&mut out).ok()?;
+    Some(out)
+}
+
+/// Builds a palette from RGB8 scanlines if the image uses 256 colors or
+/// fewer, returning the palette entries and the indexed rows. Returns
+/// `None` when the image has too many distinct colors to fit a PLTE chunk.
+fn try_palettize(rows: &[Vec<u8>], width: usize) -> Option<(Vec<[u8; 3]>, Vec<Vec<u8>>)> {
+    let mut palette = Vec::new();
+    let mut index_of: HashMap<[u8; 3], u8> = HashMap::new();
+    let mut indexed_rows = Vec::with_capacity(rows.len());
+
+    for row in rows {
+        let mut indexed = Vec::with_capacity(width);
+        for px in row.chunks_exact(3) {
+            let color = [px[0], px[1], px[2]];
+            let idx = match index_of.get(&color) {
+                Some(&idx) => idx,
+                None => {
+                    if palette.len() == 256 {
+                        return None;
+                    }
+                    let idx = palette.len() as u8;
+                    palette.push(color);
+                    index_of.insert(color, idx);
+                    idx
+                }
+            };
+            indexed.push(idx);
+        }
+        indexed_rows.push(indexed);
+    }
+
+    Some((palette, indexed_rows))
+}
+
+/// Runs the lossless optimization pass over an already-encoded PNG buffer,
+/// returning a smaller (or equal) spec-valid PNG with identical pixels.
+/// Falls back to stripping ancillary chunks only if the PNG layout isn't one
+/// this optimizer understands (interlaced, indexed, etc.) or the PNG carries
+/// chunks that must be kept (gAMA, sRGB, iCCP, tRNS, ...).
+pub fn optimize(png: &[u8]) -> Vec<u8> {
+    let Some(chunks) = parse_chunks(png) else {
+        return png.to_vec();
+    };
+
+    let Some(ihdr) = chunks.iter().find(|c| &c.kind == b"IHDR") else {
+        return png.to_vec();
+    };
+    if ihdr.data.len() < 13 {
+        return png.to_vec();
+    }
+    let width = u32::from_be_bytes(ihdr.data[0..4].try_into().unwrap()) as usize;
+    let height = u32::from_be_bytes(ihdr.data[4..8].try_into().unwrap()) as usize;
+    let bit_depth = ihdr.data[8];
+    let color_type = ihdr.data[9];
+    let interlace = ihdr.data[12];
+
+    // Only the common, non-interlaced 8-bit RGB/RGBA case is optimized here;
+    // anything else is passed through with ancillary chunks stripped.
+    if interlace != 0 || bit_depth != 8 || (color_type != 2 && color_type != 6) {
+        return strip_ancillary_only(&chunks);
+    }
+
+    // The re-encode below writes only IHDR/PLTE/IDAT/IEND, so any chunk that
+    // `is_ancillary` does not allow dropping sends us down the strip-only path.
+    let must_keep = |c: &Chunk| !is_ancillary(&c.kind) && !matches!(&c.kind, b"IHDR" | b"PLTE" | b"IDAT" | b"IEND");
+    if chunks.iter().any(must_keep) {
+        return strip_ancillary_only(&chunks);
+    }
+
+    let channels = if color_type == 6 { 4 } else { 3 };
+    let bpp = channels;
+    let stride = width * channels;
+
+    let idat: Vec<u8> = chunks
+        .iter()
+        .filter(|c| &c.kind == b"IDAT")
+        .flat_map(|c| c.data.iter().copied())
+        .collect();
+    let Some(raw) = inflate(&idat, height * (stride + 1)) else {
+        return strip_ancillary_only(&chunks);
+    };
+
+    let rows = unfilter_scanlines(&raw, stride, bpp);
+    if rows.len() != height {
+        return strip_ancillary_only(&chunks);
+    }
+
+    // Only RGB8 (no alpha) can be safely palettized without losing the
+    // ability to round-trip to identical pixels through a PLTE chunk.
+    let palettized = if color_type == 2 {
+        try_palettize(&rows, width)
+    } else {
+        None
+    };
+
+    let mut out = Vec::with_capacity(png.len());
+    out.extend_from_slice(&PNG_SIGNATURE);
+
+    if let Some((palette, indexed_rows)) = palettized {
+        let mut new_ihdr = ihdr.data.clone();
+        new_ihdr[9] = 3; // color type: indexed
+        write_chunk(&mut out, b"IHDR", &new_ihdr);
+
+        let mut plte = Vec::with_capacity(palette.len() * 3);
+        for color in &palette {
+            plte.extend_from_slice(color);
+        }
+        write_chunk(&mut out, b"PLTE", &plte);
+
+        let filtered = best_filtered_stream(&indexed_rows, 1);
+        let compressed = deflate(&filtered);
+        write_chunk(&mut out, b"IDAT", &compressed);
+    } else {
+        write_chunk(&mut out, b"IHDR", &ihdr.data);
+        let filtered = best_filtered_stream(&rows, bpp);
+        let compressed = deflate(&filtered);
+
+        // Keep whichever is smaller: our re-filtered stream or the encoder's
+        // original IDAT payload (re-optimizing can lose to a good encoder).
+        if compressed.len() < idat.len() {
+            write_chunk(&mut out, b"IDAT", &compressed);
+        } else {
+            write_chunk(&mut out, b"IDAT", &idat);
+        }
+    }
+
+    write_chunk(&mut out, b"IEND", &[]);
+
+    if out.len() < png.len() {
+        out
+    } else {
+        strip_ancillary_only(&chunks)
+    }
+}
+
+/// Re-packs the chunk list dropping ancillary metadata only, without
+/// touching IHDR/PLTE/IDAT pixel data.
+fn strip_ancillary_only(chunks: &[Chunk]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&PNG_SIGNATURE);
+    for chunk in chunks {
+        if is_ancillary(&chunk.kind) {
+            continue;
+        }
+        write_chunk(&mut out, &chunk.kind, &chunk.data);
+    }
+    out
+}